@@ -2,10 +2,8 @@ use std::process::exit;
 
 use anyhow::Result;
 use clap::command;
-use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
-    net::UnixStream,
-};
+use pomobar_rs::models::{read_message, write_message, Answer, Command};
+use tokio::net::UnixStream;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -14,28 +12,31 @@ async fn main() -> Result<()> {
     let cmd = command!()
         .subcommand(command!("status").about("Get currently pomodoro status."))
         .subcommand(command!("toggle").about("Start/Pause pomodoro."))
-        .subcommand(command!("reset").about("Reset pomodoro."));
+        .subcommand(command!("reset").about("Reset pomodoro."))
+        .subcommand(command!("skip").about("Skip to the next pomodoro state."))
+        .subcommand(command!("stop").about("Stop pomodoro, keeping today's cycle count."));
 
     let mut socket = UnixStream::connect(path).await?;
 
     let matches = cmd.clone().get_matches();
 
-    match matches.subcommand_name() {
-        Some(command) => {
-            socket.write_all(command.as_bytes()).await?;
-        }
-        None => {
+    let command = match matches.subcommand_name() {
+        Some("toggle") => Command::Toggle,
+        Some("reset") => Command::Reset,
+        Some("skip") => Command::Skip,
+        Some("stop") => Command::Stop,
+        Some("status") => Command::Status,
+        _ => {
             cmd.clone().print_help().unwrap();
             exit(127);
         }
     };
 
-    let mut buf = vec![0; 1024];
-    let content_length = socket.read(&mut buf).await.unwrap();
+    write_message(&mut socket, &command).await?;
 
-    if content_length > 0 {
-        let content = String::from_utf8(buf[..content_length].to_vec()).unwrap();
-        println!("{content}");
+    match read_message::<_, Answer>(&mut socket).await? {
+        Answer::Status(report) => println!("{}", report.view),
+        Answer::Ack => {}
     }
 
     Ok(())
@@ -2,13 +2,11 @@ use std::{path::Path, time::Duration};
 
 use anyhow::Result;
 use chrono::TimeDelta;
-use pomobar_rs::models::{send_notification, Pomobar, PomobarDispatcher};
-use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
-    net::UnixListener,
-    sync::mpsc,
-    time::sleep,
+use pomobar_rs::models::{
+    load_state, play_sound, read_message, save_state, send_notification, write_message, Answer,
+    Command, Config, Pomobar, PomobarDispatcher, StatusReport,
 };
+use tokio::{net::UnixListener, sync::mpsc, time::sleep};
 
 #[macro_use]
 extern crate tracing;
@@ -17,7 +15,9 @@ extern crate tracing;
 enum ServerEvent {
     Toggle,
     Reset,
-    Status(tokio::sync::oneshot::Sender<String>),
+    Skip,
+    Stop,
+    Status(tokio::sync::oneshot::Sender<StatusReport>),
     Tick,
 }
 
@@ -25,6 +25,9 @@ enum ServerEvent {
 async fn main() -> Result<()> {
     let path = "/tmp/pomobar.sock";
 
+    let config = Config::load();
+    debug!("Loaded config: {:?}", config);
+
     if Path::new(path).exists() {
         std::fs::remove_file(path)?;
         debug!("Removed existing socket file.");
@@ -49,27 +52,66 @@ async fn main() -> Result<()> {
     tokio::spawn(async move {
         loop {
             let (mut socket, _) = listener.accept().await.unwrap();
-            let mut buf = vec![0; 1024];
-            let n = socket.read(&mut buf).await.unwrap();
-
-            if n > 0 {
-                let command = String::from_utf8(buf[..n].to_vec()).unwrap();
-                match command.as_str() {
-                    "toggle" => socket_tx.send(ServerEvent::Toggle).await.unwrap(),
-                    "reset" => socket_tx.send(ServerEvent::Reset).await.unwrap(),
-                    _ => {
-                        let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
-                        socket_tx.send(ServerEvent::Status(resp_tx)).await.unwrap();
-                        let response = resp_rx.await.unwrap();
-                        socket.write_all(response.as_bytes()).await.unwrap();
-                    }
+
+            let command: Command = match read_message(&mut socket).await {
+                Ok(command) => command,
+                Err(err) => {
+                    warn!("Failed to read command: {err}");
+                    continue;
+                }
+            };
+
+            let answer = match command {
+                Command::Toggle => {
+                    socket_tx.send(ServerEvent::Toggle).await.unwrap();
+                    Answer::Ack
+                }
+                Command::Reset => {
+                    socket_tx.send(ServerEvent::Reset).await.unwrap();
+                    Answer::Ack
+                }
+                Command::Skip => {
+                    socket_tx.send(ServerEvent::Skip).await.unwrap();
+                    Answer::Ack
+                }
+                Command::Stop => {
+                    socket_tx.send(ServerEvent::Stop).await.unwrap();
+                    Answer::Ack
                 }
+                Command::Status => {
+                    let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+                    socket_tx.send(ServerEvent::Status(resp_tx)).await.unwrap();
+                    Answer::Status(resp_rx.await.unwrap())
+                }
+            };
+
+            if let Err(err) = write_message(&mut socket, &answer).await {
+                warn!("Failed to write answer: {err}");
             }
         }
     });
 
     // --- Main Event Loop ---
-    let mut pomodoro = PomobarDispatcher::Idle(Pomobar::new());
+    let mut pomodoro = load_state().unwrap_or_else(|| PomobarDispatcher::Idle(Pomobar::new()));
+
+    // A timer restored after a long gap may have run out while the daemon was
+    // down. Advance it (a `Paused` snapshot with nothing left on the clock is
+    // resumed and then finished) so it resumes somewhere live rather than
+    // appearing stuck. Each transition starts the next state's clock now, so
+    // downtime is not charged to the states that follow the expired one.
+    while pomodoro.get_remaining_time(&config).eq(&TimeDelta::seconds(0)) {
+        pomodoro = match pomodoro {
+            PomobarDispatcher::Work(p) => p.finish(&config),
+            PomobarDispatcher::ShortBreak(p) => PomobarDispatcher::Work(p.finish(&config)),
+            PomobarDispatcher::LongBreak(p) => PomobarDispatcher::Work(p.finish(&config)),
+            PomobarDispatcher::Paused(p) => PomobarDispatcher::Work(p.resume(&config)),
+            // `Idle` only lands here if `work` is configured to zero; bail out
+            // so we never spin forever.
+            PomobarDispatcher::Idle(_) => break,
+        };
+    }
+    save_state(&pomodoro);
+    debug!("Resumed state: {}", pomodoro.get_state_name());
 
     loop {
         let event = rx.recv().await.unwrap();
@@ -77,32 +119,53 @@ async fn main() -> Result<()> {
         match event {
             ServerEvent::Toggle => {
                 pomodoro = match pomodoro {
-                    PomobarDispatcher::Idle(p) => PomobarDispatcher::Work(p.start()),
-                    PomobarDispatcher::Work(p) => PomobarDispatcher::Paused(p.pause()),
-                    PomobarDispatcher::Paused(p) => PomobarDispatcher::Work(p.resume()),
+                    PomobarDispatcher::Idle(p) => PomobarDispatcher::Work(p.start(&config)),
+                    PomobarDispatcher::Work(p) => PomobarDispatcher::Paused(p.pause(&config)),
+                    PomobarDispatcher::Paused(p) => PomobarDispatcher::Work(p.resume(&config)),
                     // Breaks cannot be toggled, they must finish.
                     PomobarDispatcher::ShortBreak(_) => pomodoro,
                     PomobarDispatcher::LongBreak(_) => pomodoro,
                 };
+                play_sound(&config);
+                save_state(&pomodoro);
                 debug!("Toggled state to: {}", pomodoro.get_state_name());
             }
             ServerEvent::Reset => {
-                send_notification("Reset timer.");
+                send_notification(&config, "Reset timer.");
+                play_sound(&config);
                 pomodoro = PomobarDispatcher::Idle(Pomobar::new());
+                save_state(&pomodoro);
                 debug!("State reset to Idle.");
             }
+            ServerEvent::Skip => {
+                pomodoro = pomodoro.skip(&config);
+                play_sound(&config);
+                save_state(&pomodoro);
+                debug!("Skipped to state: {}", pomodoro.get_state_name());
+            }
+            ServerEvent::Stop => {
+                pomodoro = pomodoro.stop(&config);
+                play_sound(&config);
+                save_state(&pomodoro);
+                debug!("Stopped to state: {}", pomodoro.get_state_name());
+            }
             ServerEvent::Status(resp_tx) => {
-                let json_content = serde_json::to_string(&pomodoro).unwrap();
-                resp_tx.send(json_content).unwrap();
+                let report = StatusReport {
+                    pomodoro: pomodoro.clone(),
+                    view: pomodoro.to_view(&config),
+                };
+                resp_tx.send(report).unwrap();
             }
             ServerEvent::Tick => {
-                if pomodoro.get_remaining_time().eq(&TimeDelta::seconds(0)) {
+                if pomodoro.get_remaining_time(&config).eq(&TimeDelta::seconds(0)) {
                     pomodoro = match pomodoro {
-                        PomobarDispatcher::Work(p) => p.finish(),
-                        PomobarDispatcher::ShortBreak(p) => PomobarDispatcher::Work(p.finish()),
-                        PomobarDispatcher::LongBreak(p) => PomobarDispatcher::Work(p.finish()),
+                        PomobarDispatcher::Work(p) => p.finish(&config),
+                        PomobarDispatcher::ShortBreak(p) => PomobarDispatcher::Work(p.finish(&config)),
+                        PomobarDispatcher::LongBreak(p) => PomobarDispatcher::Work(p.finish(&config)),
                         _ => pomodoro, // No timed action for Idle or Paused
                     };
+                    play_sound(&config);
+                    save_state(&pomodoro);
                     debug!(
                         "Timer finished, transitioned to: {}",
                         pomodoro.get_state_name()
@@ -1,10 +1,138 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::Result;
 use chrono::{Duration, Local, NaiveDateTime, TimeDelta};
+use directories::ProjectDirs;
 use notify_rust::{Notification, Urgency};
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+// --- Configuration ---
+
+/// Serde glue for reading/writing a [`chrono::Duration`] as a humantime
+/// string (e.g. `"25m"`), so that settings stay readable in the TOML file.
+mod humantime_duration {
+    use chrono::Duration;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let std = value
+            .to_std()
+            .map_err(|e| serde::ser::Error::custom(e.to_string()))?;
+        humantime::format_duration(std)
+            .to_string()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = String::deserialize(deserializer)?;
+        let std = humantime::parse_duration(&raw).map_err(serde::de::Error::custom)?;
+        Duration::from_std(std).map_err(serde::de::Error::custom)
+    }
+}
+
+/// User-tunable settings, deserialized from `~/.config/pomobar/settings.toml`.
+///
+/// Durations are written in humantime form (`"25m"`, `"5m"`, `"15m"`) and the
+/// long-break cadence is controlled by `cycles_till_long`. All fields fall back
+/// to the classic Pomodoro defaults when the file (or an individual key) is
+/// absent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    #[serde(with = "humantime_duration")]
+    pub work: Duration,
+    #[serde(with = "humantime_duration")]
+    pub short_break: Duration,
+    #[serde(with = "humantime_duration")]
+    pub long_break: Duration,
+    pub cycles_till_long: u32,
+    /// Whether to emit a desktop notification on each state transition. Toggled
+    /// independently of [`sound_file`](Self::sound_file).
+    pub notifications: bool,
+    /// Optional audio file played on every state transition. When unset, no
+    /// sound is played. Toggled independently of [`notifications`](Self::notifications).
+    pub sound_file: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            work: Duration::minutes(25),
+            short_break: Duration::minutes(5),
+            long_break: Duration::minutes(15),
+            cycles_till_long: 4,
+            notifications: true,
+            sound_file: None,
+        }
+    }
+}
+
+impl Config {
+    /// Load the config from the XDG config directory, falling back to the
+    /// defaults when the file is missing or cannot be parsed.
+    pub fn load() -> Self {
+        let Some(dirs) = ProjectDirs::from("", "", "pomobar") else {
+            return Config::default();
+        };
+
+        let path = dirs.config_dir().join("settings.toml");
+        match fs::read_to_string(&path) {
+            Ok(contents) => match toml::from_str::<Config>(&contents) {
+                Ok(config) => config.validated(),
+                Err(err) => {
+                    tracing::warn!(
+                        "Failed to parse {}: {err}; falling back to defaults",
+                        path.display()
+                    );
+                    Config::default()
+                }
+            },
+            Err(_) => Config::default(),
+        }
+    }
+
+    /// Clamp out-of-range values to sane minimums. In particular a
+    /// `cycles_till_long` of `0` would disable long breaks forever (since
+    /// `is_multiple_of(0)` is never true for a positive cycle count), so it is
+    /// forced back to `1`.
+    fn validated(mut self) -> Self {
+        if self.cycles_till_long == 0 {
+            tracing::warn!("cycles_till_long must be >= 1; clamping to 1");
+            self.cycles_till_long = 1;
+        }
+        self
+    }
+
+    /// A one-line, human-readable summary of the configured session lengths,
+    /// e.g. `"Work 25m / Break 5m / Long 15m"`, suitable for a waybar tooltip.
+    pub fn summary(&self) -> String {
+        let fmt = |d: Duration| match d.to_std() {
+            Ok(std) => humantime::format_duration(std).to_string(),
+            Err(_) => "0s".to_string(),
+        };
+        format!(
+            "Work {} / Break {} / Long {}",
+            fmt(self.work),
+            fmt(self.short_break),
+            fmt(self.long_break),
+        )
+    }
+}
 
 // --- Notifications ---
 
-pub fn send_notification(summary: &str) {
+pub fn send_notification(config: &Config, summary: &str) {
+    if !config.notifications {
+        return;
+    }
+
     Notification::new()
         .summary(summary)
         .urgency(Urgency::Low)
@@ -14,15 +142,91 @@ pub fn send_notification(summary: &str) {
         .unwrap();
 }
 
+// --- State persistence ---
+
+/// Location of the on-disk snapshot used to survive daemon restarts.
+fn state_path() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "pomobar").map(|dirs| dirs.data_dir().join("state.json"))
+}
+
+/// Persist the current timer so a restart can resume where it left off.
+///
+/// Failures are logged rather than propagated: losing a snapshot should never
+/// take down the running daemon.
+pub fn save_state(pomodoro: &PomobarDispatcher) {
+    let Some(path) = state_path() else {
+        return;
+    };
+
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+
+    match serde_json::to_string(pomodoro) {
+        Ok(json) => {
+            if let Err(err) = fs::write(&path, json) {
+                tracing::warn!("Failed to persist timer state: {err}");
+            }
+        }
+        Err(err) => tracing::warn!("Failed to serialize timer state: {err}"),
+    }
+}
+
+/// Attempt to restore a previously persisted timer, returning `None` when no
+/// readable snapshot exists.
+pub fn load_state() -> Option<PomobarDispatcher> {
+    let path = state_path()?;
+    let contents = fs::read_to_string(&path).ok()?;
+    match serde_json::from_str(&contents) {
+        Ok(pomodoro) => Some(pomodoro),
+        Err(err) => {
+            tracing::warn!("Failed to load persisted timer state: {err}");
+            None
+        }
+    }
+}
+
+// --- Audio alerts ---
+
+/// Play the configured alert sound, if any, on a detached output stream.
+///
+/// This is intentionally decoupled from [`send_notification`] so the two can
+/// be enabled independently, and so a missing file or unavailable audio device
+/// degrades to a `tracing` warning rather than bringing the daemon down.
+pub fn play_sound(config: &Config) {
+    let Some(path) = config.sound_file.clone() else {
+        return;
+    };
+
+    // Playback (and the stream it depends on) lives on its own thread so the
+    // event loop is never blocked waiting for the clip to finish.
+    std::thread::spawn(move || {
+        if let Err(err) = play_file(&path) {
+            tracing::warn!("Failed to play alert sound {}: {err}", path.display());
+        }
+    });
+}
+
+fn play_file(path: &std::path::Path) -> anyhow::Result<()> {
+    let (_stream, handle) = rodio::OutputStream::try_default()?;
+    let file = std::io::BufReader::new(fs::File::open(path)?);
+    let sink = rodio::Sink::try_new(&handle)?;
+    sink.append(rodio::Decoder::new(file)?);
+    sink.sleep_until_end();
+    Ok(())
+}
+
 // --- State Marker Traits and Structs ---
 
 /// A trait for states that have a defined duration.
 pub trait TimedState {
-    fn duration(&self) -> Duration;
+    fn duration(&self, config: &Config) -> Duration;
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Idle;
+pub struct Idle {
+    pub cycles: u32,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Work {
@@ -31,8 +235,8 @@ pub struct Work {
 }
 
 impl TimedState for Work {
-    fn duration(&self) -> Duration {
-        Duration::minutes(25)
+    fn duration(&self, config: &Config) -> Duration {
+        config.work
     }
 }
 
@@ -49,8 +253,8 @@ pub struct ShortBreak {
 }
 
 impl TimedState for ShortBreak {
-    fn duration(&self) -> Duration {
-        Duration::minutes(5)
+    fn duration(&self, config: &Config) -> Duration {
+        config.short_break
     }
 }
 
@@ -61,11 +265,47 @@ pub struct LongBreak {
 }
 
 impl TimedState for LongBreak {
-    fn duration(&self) -> Duration {
-        Duration::minutes(15)
+    fn duration(&self, config: &Config) -> Duration {
+        config.long_break
+    }
+}
+
+/// Running states that carry a live cycle count. Centralising the accessor
+/// lets the halt-into-`Idle` transition be written once for every state
+/// instead of copied per-type.
+trait Cycled {
+    fn cycles(&self) -> u32;
+}
+
+impl Cycled for Work {
+    fn cycles(&self) -> u32 {
+        self.cycles
     }
 }
 
+impl Cycled for Paused {
+    fn cycles(&self) -> u32 {
+        self.cycles
+    }
+}
+
+impl Cycled for ShortBreak {
+    fn cycles(&self) -> u32 {
+        self.cycles
+    }
+}
+
+impl Cycled for LongBreak {
+    fn cycles(&self) -> u32 {
+        self.cycles
+    }
+}
+
+/// The two break states, which share the "back to focus" skip transition.
+trait BreakState: Cycled {}
+impl BreakState for ShortBreak {}
+impl BreakState for LongBreak {}
+
 // --- Generic Pomodoro Timer ---
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -78,15 +318,24 @@ pub struct Pomobar<S> {
 // Transitions from Idle
 impl Pomobar<Idle> {
     pub fn new() -> Self {
-        Pomobar { state: Idle }
+        Pomobar {
+            state: Idle { cycles: 0 },
+        }
+    }
+
+    /// Halt into `Idle` carrying `cycles` completed pomodoros for the day.
+    fn halted(cycles: u32) -> Pomobar<Idle> {
+        Pomobar {
+            state: Idle { cycles },
+        }
     }
 
-    pub fn start(self) -> Pomobar<Work> {
-        send_notification("Time to focus!");
+    pub fn start(self, config: &Config) -> Pomobar<Work> {
+        send_notification(config, "Time to focus!");
         Pomobar {
             state: Work {
                 started_at: Local::now().naive_local(),
-                cycles: 0,
+                cycles: self.state.cycles,
             },
         }
     }
@@ -100,10 +349,20 @@ impl Default for Pomobar<Idle> {
 
 // Transitions from Working
 impl Pomobar<Work> {
-    pub fn pause(self) -> Pomobar<Paused> {
-        send_notification("Pomodoro paused.");
+    /// Begin a fresh focus session carrying `cycles` completed pomodoros.
+    fn focus(cycles: u32) -> Pomobar<Work> {
+        Pomobar {
+            state: Work {
+                started_at: Local::now().naive_local(),
+                cycles,
+            },
+        }
+    }
+
+    pub fn pause(self, config: &Config) -> Pomobar<Paused> {
+        send_notification(config, "Pomodoro paused.");
         let elapsed = Local::now().naive_local() - self.state.started_at;
-        let remaining = self.state.duration() - elapsed;
+        let remaining = self.state.duration(config) - elapsed;
         Pomobar {
             state: Paused {
                 remaining: if remaining > Duration::zero() {
@@ -116,34 +375,53 @@ impl Pomobar<Work> {
         }
     }
 
-    pub fn finish(self) -> PomobarDispatcher {
-        send_notification("Time for a break!");
-        let new_cycles = self.state.cycles + 1;
-        if new_cycles.is_multiple_of(4) {
+    /// Enter the break that follows a work session holding `cycles` completed
+    /// pomodoros, picking a long break on the configured cadence. Shared by
+    /// [`finish`](Self::finish) and [`skip`](Self::skip) so the cadence logic
+    /// lives in exactly one place.
+    fn into_break(cycles: u32, config: &Config) -> PomobarDispatcher {
+        // `cycles == 0` (e.g. a skipped first session) hasn't earned a long
+        // break even though `0.is_multiple_of(n)` is `true`, so require a
+        // positive count before honouring the cadence.
+        if cycles > 0 && cycles.is_multiple_of(config.cycles_till_long) {
             PomobarDispatcher::LongBreak(Pomobar {
                 state: LongBreak {
                     started_at: Local::now().naive_local(),
-                    cycles: new_cycles,
+                    cycles,
                 },
             })
         } else {
             PomobarDispatcher::ShortBreak(Pomobar {
                 state: ShortBreak {
                     started_at: Local::now().naive_local(),
-                    cycles: new_cycles,
+                    cycles,
                 },
             })
         }
     }
+
+    pub fn finish(self, config: &Config) -> PomobarDispatcher {
+        send_notification(config, "Time for a break!");
+        Self::into_break(self.state.cycles + 1, config)
+    }
+
+    /// Cut the current work session short and jump straight to the break.
+    ///
+    /// Unlike [`finish`](Self::finish), an abandoned focus block is *not*
+    /// counted as a completed pomodoro, so it advances neither the day's total
+    /// nor the long-break cadence.
+    pub fn skip(self, config: &Config) -> PomobarDispatcher {
+        send_notification(config, "Skipping to break.");
+        Self::into_break(self.state.cycles, config)
+    }
 }
 
 // Transitions from Paused
 impl Pomobar<Paused> {
-    pub fn resume(self) -> Pomobar<Work> {
-        send_notification("Resuming pomodoro.");
+    pub fn resume(self, config: &Config) -> Pomobar<Work> {
+        send_notification(config, "Resuming pomodoro.");
         // To keep the original end time, we calculate a new start time.
-        let new_started_at =
-            Local::now().naive_local() - (Duration::minutes(25) - self.state.remaining);
+        let new_started_at = Local::now().naive_local() - (config.work - self.state.remaining);
 
         Pomobar {
             state: Work {
@@ -156,26 +434,35 @@ impl Pomobar<Paused> {
 
 // Transitions from Breaks
 impl Pomobar<ShortBreak> {
-    pub fn finish(self) -> Pomobar<Work> {
-        send_notification("Break is over. Time to focus!");
-        Pomobar {
-            state: Work {
-                started_at: Local::now().naive_local(),
-                cycles: self.state.cycles,
-            },
-        }
+    pub fn finish(self, config: &Config) -> Pomobar<Work> {
+        send_notification(config, "Break is over. Time to focus!");
+        Pomobar::<Work>::focus(self.state.cycles)
     }
 }
 
 impl Pomobar<LongBreak> {
-    pub fn finish(self) -> Pomobar<Work> {
-        send_notification("Long break is over. Time to get back to it!");
-        Pomobar {
-            state: Work {
-                started_at: Local::now().naive_local(),
-                cycles: self.state.cycles,
-            },
-        }
+    pub fn finish(self, config: &Config) -> Pomobar<Work> {
+        send_notification(config, "Long break is over. Time to get back to it!");
+        Pomobar::<Work>::focus(self.state.cycles)
+    }
+}
+
+// Halting into `Idle` is identical for every timed state: keep the day's
+// completed cycle count and drop the running clock.
+impl<S: Cycled> Pomobar<S> {
+    /// Halt into `Idle`, keeping the completed cycle count for the day.
+    pub fn stop(self, config: &Config) -> Pomobar<Idle> {
+        send_notification(config, "Timer stopped.");
+        Pomobar::<Idle>::halted(self.state.cycles())
+    }
+}
+
+// Both breaks share the same "cut it short and get back to focus" transition.
+impl<S: BreakState> Pomobar<S> {
+    /// Cut the break short and get straight back to focus.
+    pub fn skip(self, config: &Config) -> Pomobar<Work> {
+        send_notification(config, "Skipping break. Time to focus!");
+        Pomobar::<Work>::focus(self.state.cycles())
     }
 }
 
@@ -203,11 +490,11 @@ struct StatusView<'view> {
 }
 
 impl PomobarDispatcher {
-    pub fn get_remaining_time(&self) -> Duration {
+    pub fn get_remaining_time(&self, config: &Config) -> Duration {
         match self {
             PomobarDispatcher::Work(p) => {
                 let elapsed = Local::now().naive_local() - p.state.started_at;
-                let remaining = p.state.duration() - elapsed;
+                let remaining = p.state.duration(config) - elapsed;
                 if remaining < Duration::zero() {
                     Duration::zero()
                 } else {
@@ -217,7 +504,7 @@ impl PomobarDispatcher {
             PomobarDispatcher::Paused(p) => p.state.remaining,
             PomobarDispatcher::ShortBreak(p) => {
                 let elapsed = Local::now().naive_local() - p.state.started_at;
-                let remaining = p.state.duration() - elapsed;
+                let remaining = p.state.duration(config) - elapsed;
                 if remaining < Duration::zero() {
                     Duration::zero()
                 } else {
@@ -226,14 +513,14 @@ impl PomobarDispatcher {
             }
             PomobarDispatcher::LongBreak(p) => {
                 let elapsed = Local::now().naive_local() - p.state.started_at;
-                let remaining = p.state.duration() - elapsed;
+                let remaining = p.state.duration(config) - elapsed;
                 if remaining < Duration::zero() {
                     Duration::zero()
                 } else {
                     remaining
                 }
             }
-            PomobarDispatcher::Idle(_) => Duration::minutes(25),
+            PomobarDispatcher::Idle(_) => config.work,
         }
     }
 
@@ -249,7 +536,7 @@ impl PomobarDispatcher {
 
     pub fn get_cycles(&self) -> u32 {
         match self {
-            PomobarDispatcher::Idle(_) => 0,
+            PomobarDispatcher::Idle(p) => p.state.cycles,
             PomobarDispatcher::Work(p) => p.state.cycles,
             PomobarDispatcher::Paused(p) => p.state.cycles,
             PomobarDispatcher::ShortBreak(p) => p.state.cycles,
@@ -257,10 +544,33 @@ impl PomobarDispatcher {
         }
     }
 
-    pub fn to_view(&self) -> String {
-        let mins = self.get_remaining_time().num_minutes();
+    /// End the current timed state immediately and advance to the next one.
+    /// `Idle` and `Paused` have nothing to skip and are left untouched.
+    pub fn skip(self, config: &Config) -> Self {
+        match self {
+            PomobarDispatcher::Work(p) => p.skip(config),
+            PomobarDispatcher::ShortBreak(p) => PomobarDispatcher::Work(p.skip(config)),
+            PomobarDispatcher::LongBreak(p) => PomobarDispatcher::Work(p.skip(config)),
+            other => other,
+        }
+    }
+
+    /// Halt back to `Idle`, preserving the day's cycle count instead of
+    /// zeroing it the way a reset does.
+    pub fn stop(self, config: &Config) -> Self {
+        match self {
+            PomobarDispatcher::Work(p) => PomobarDispatcher::Idle(p.stop(config)),
+            PomobarDispatcher::Paused(p) => PomobarDispatcher::Idle(p.stop(config)),
+            PomobarDispatcher::ShortBreak(p) => PomobarDispatcher::Idle(p.stop(config)),
+            PomobarDispatcher::LongBreak(p) => PomobarDispatcher::Idle(p.stop(config)),
+            PomobarDispatcher::Idle(p) => PomobarDispatcher::Idle(p),
+        }
+    }
+
+    pub fn to_view(&self, config: &Config) -> String {
+        let mins = self.get_remaining_time(config).num_minutes();
         let secs = self
-            .get_remaining_time()
+            .get_remaining_time(config)
             .checked_sub(&TimeDelta::minutes(mins))
             .unwrap()
             .num_seconds();
@@ -269,9 +579,82 @@ impl PomobarDispatcher {
             alt: self.get_state_name(),
             class: self.get_state_name(),
             text: &format!("{mins:02}:{secs:02}"),
-            tooltip: &format!("Completed {} pomodoros.", self.get_cycles()),
+            tooltip: &format!(
+                "Completed {} pomodoros.\n{}",
+                self.get_cycles(),
+                config.summary()
+            ),
         };
 
         serde_json::to_string(&view).unwrap()
     }
 }
+
+// --- Socket Protocol ---
+
+/// A request sent by the client to the daemon.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Command {
+    Toggle,
+    Reset,
+    Skip,
+    Stop,
+    Status,
+}
+
+/// The daemon's reply to a [`Command`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Answer {
+    /// The command was accepted; it carries no payload.
+    Ack,
+    /// A full status report, returned in response to [`Command::Status`].
+    Status(StatusReport),
+}
+
+/// The structured payload of a status response: the complete state machine
+/// plus the rendered waybar view.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusReport {
+    pub pomodoro: PomobarDispatcher,
+    pub view: String,
+}
+
+/// Write a value as a length-prefixed CBOR frame: a big-endian `u32` byte
+/// count followed by the encoded body, so the reader knows exactly how much to
+/// consume regardless of payload size.
+pub async fn write_message<W, T>(writer: &mut W, message: &T) -> Result<()>
+where
+    W: AsyncWriteExt + Unpin,
+    T: Serialize,
+{
+    let body = serde_cbor::to_vec(message)?;
+    let len = u32::try_from(body.len())?;
+    writer.write_all(&len.to_be_bytes()).await?;
+    writer.write_all(&body).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Upper bound on a single frame's body, guarding against a corrupt length
+/// prefix triggering a huge allocation. Messages on this socket are tiny, so
+/// 1 MiB leaves ample headroom.
+const MAX_FRAME_LEN: usize = 1024 * 1024;
+
+/// Read a single length-prefixed CBOR frame written by [`write_message`].
+pub async fn read_message<R, T>(reader: &mut R) -> Result<T>
+where
+    R: AsyncReadExt + Unpin,
+    T: DeserializeOwned,
+{
+    let mut len_buf = [0u8; 4];
+    reader.read_exact(&mut len_buf).await?;
+    let len = u32::from_be_bytes(len_buf) as usize;
+
+    if len > MAX_FRAME_LEN {
+        anyhow::bail!("frame length {len} exceeds maximum of {MAX_FRAME_LEN} bytes");
+    }
+
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body).await?;
+    Ok(serde_cbor::from_slice(&body)?)
+}